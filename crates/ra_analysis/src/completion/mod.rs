@@ -0,0 +1,179 @@
+mod reference_completion;
+
+use hir::source_binder;
+use ra_db::FilePosition;
+use ra_editor::find_node_at_offset;
+use ra_syntax::{ast, AstNode};
+use ra_text_edit::{AtomTextEdit, TextEdit};
+
+use crate::{db::RootDatabase, Cancelable};
+
+/// The entry point editors actually call: finds the `NameRef` at `position`
+/// and dispatches to the classification/completion logic in
+/// `reference_completion`, returning results in the stable, kind-aware
+/// order `Completions::into_sorted_vec` produces.
+pub(crate) fn completions(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Cancelable<Option<Vec<CompletionItem>>> {
+    let mut file = db.source_file(position.file_id);
+    if find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset).is_none() {
+        // Right after `foo.` or `Foo::` there is no `NameRef` yet, so insert a
+        // placeholder identifier to get one to classify.
+        let edit = AtomTextEdit::insert(position.offset, "intellijRulezz".to_string());
+        file = file.reparse(&edit);
+    }
+    let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+    let module = match source_binder::module_from_position(db, position)? {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    let mut acc = Completions::default();
+    reference_completion::completions(&mut acc, db, &module, &file, name_ref)?;
+    Ok(Some(acc.into_sorted_vec()))
+}
+
+/// The kind of thing a `CompletionItem` refers to. Editors use this to pick
+/// an icon and, together with `CompletionItem::relevance`, a sort order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionItemKind {
+    Function,
+    Struct,
+    Enum,
+    Module,
+    Local,
+    Keyword,
+    Snippet,
+    Field,
+    Method,
+    EnumVariant,
+    Const,
+}
+
+impl CompletionItemKind {
+    /// Lower numbers sort first. Locals and other in-scope items should
+    /// read as more relevant than the always-available keyword/snippet
+    /// completions.
+    fn sort_priority(self) -> i32 {
+        match self {
+            CompletionItemKind::Local | CompletionItemKind::Field | CompletionItemKind::Method => 0,
+            CompletionItemKind::Function
+            | CompletionItemKind::Struct
+            | CompletionItemKind::Enum
+            | CompletionItemKind::Module
+            | CompletionItemKind::EnumVariant
+            | CompletionItemKind::Const => 1,
+            CompletionItemKind::Snippet => 2,
+            CompletionItemKind::Keyword => 3,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CompletionItem {
+    label: String,
+    lookup: Option<String>,
+    snippet: Option<String>,
+    kind: Option<CompletionItemKind>,
+    relevance: i32,
+    /// An edit to apply alongside the main insertion, e.g. adding a `use`
+    /// for a name that isn't in scope yet.
+    additional_edit: Option<TextEdit>,
+}
+
+impl CompletionItem {
+    pub(crate) fn new(label: impl Into<String>) -> CompletionItem {
+        CompletionItem {
+            label: label.into(),
+            lookup: None,
+            snippet: None,
+            kind: None,
+            relevance: 0,
+            additional_edit: None,
+        }
+    }
+    pub(crate) fn lookup_by(mut self, lookup: impl Into<String>) -> CompletionItem {
+        self.lookup = Some(lookup.into());
+        self
+    }
+    pub(crate) fn snippet(mut self, snippet: impl Into<String>) -> CompletionItem {
+        self.snippet = Some(snippet.into());
+        self
+    }
+    pub(crate) fn kind(mut self, kind: CompletionItemKind) -> CompletionItem {
+        self.kind = Some(kind);
+        self
+    }
+    /// Higher values sort earlier, within the bucket assigned by `kind`.
+    pub(crate) fn set_relevance(mut self, relevance: i32) -> CompletionItem {
+        self.relevance = relevance;
+        self
+    }
+    pub(crate) fn with_additional_edit(mut self, edit: TextEdit) -> CompletionItem {
+        self.additional_edit = Some(edit);
+        self
+    }
+    pub(crate) fn build(self) -> CompletionItem {
+        self
+    }
+    pub(crate) fn add_to(self, acc: &mut Completions) {
+        acc.add(self)
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Accumulates `CompletionItem`s produced while walking the syntax tree and
+/// produces a stably sorted result.
+#[derive(Default, Debug)]
+pub struct Completions {
+    buf: Vec<CompletionItem>,
+}
+
+impl Completions {
+    pub(crate) fn add(&mut self, item: CompletionItem) {
+        self.buf.push(item);
+    }
+    pub(crate) fn add_all<I>(&mut self, items: I)
+    where
+        I: IntoIterator<Item = CompletionItem>,
+    {
+        items.into_iter().for_each(|item| self.add(item));
+    }
+    pub(crate) fn into_sorted_vec(self) -> Vec<CompletionItem> {
+        let mut buf = self.buf;
+        buf.sort_by_key(|item| {
+            let kind_priority = item.kind.map(CompletionItemKind::sort_priority).unwrap_or(1);
+            (kind_priority, -item.relevance)
+        });
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locals_sort_before_keywords_and_snippets() {
+        let mut acc = Completions::default();
+        CompletionItem::new("pd")
+            .kind(CompletionItemKind::Snippet)
+            .add_to(&mut acc);
+        CompletionItem::new("if")
+            .kind(CompletionItemKind::Keyword)
+            .add_to(&mut acc);
+        CompletionItem::new("x")
+            .kind(CompletionItemKind::Local)
+            .add_to(&mut acc);
+
+        let labels: Vec<&str> = acc.into_sorted_vec().iter().map(|it| it.label()).collect();
+        assert_eq!(labels, vec!["x", "pd", "if"]);
+    }
+}
@@ -2,7 +2,7 @@ use rustc_hash::{FxHashSet};
 use ra_editor::find_node_at_offset;
 use ra_syntax::{
     algo::visit::{visitor, Visitor},
-    SourceFileNode, AstNode,
+    SourceFileNode, AstNode, TextUnit,
     ast::{self, LoopBodyOwner},
     SyntaxKind::*,
 };
@@ -10,10 +10,12 @@ use hir::{
     self,
     FnScopes, Def, Path
 };
+use ra_text_edit::{TextEdit, TextEditBuilder};
 
 use crate::{
     db::RootDatabase,
-    completion::{CompletionItem, Completions},
+    completion::{CompletionItem, CompletionItemKind, Completions},
+    symbol_index::SymbolIndex,
     Cancelable
 };
 
@@ -31,29 +33,38 @@ pub(super) fn completions(
 
     match kind {
         NameRefKind::LocalRef { enclosing_fn } => {
+            let mut in_scope = FxHashSet::default();
+
             if let Some(fn_def) = enclosing_fn {
                 let scopes = FnScopes::new(fn_def);
-                complete_fn(name_ref, &scopes, acc);
+                complete_fn(name_ref, &scopes, acc, &mut in_scope);
                 complete_expr_keywords(&file, fn_def, name_ref, acc);
                 complete_expr_snippets(acc);
             }
 
             let module_scope = module.scope(db)?;
-            module_scope
-                .entries()
-                .filter(|(_name, res)| {
-                    // Don't expose this item
-                    match res.import {
-                        None => true,
-                        Some(import) => {
-                            let range = import.range(db, module.source().file_id());
-                            !range.is_subrange(&name_ref.syntax().range())
-                        }
+            for (name, res) in module_scope.entries() {
+                // Don't expose this item
+                if let Some(import) = res.import {
+                    let range = import.range(db, module.source().file_id());
+                    if range.is_subrange(&name_ref.syntax().range()) {
+                        continue;
+                    }
+                }
+                in_scope.insert(name.to_string());
+                let mut item = CompletionItem::new(name.to_string());
+                if let Some(def_id) = res.def_id {
+                    if let Some(kind) = def_kind(db, def_id)? {
+                        item = item.kind(kind);
                     }
-                })
-                .for_each(|(name, _res)| CompletionItem::new(name.to_string()).add_to(acc));
+                }
+                item.add_to(acc);
+            }
+
+            complete_auto_import(acc, db, module, file, name_ref, &in_scope)?;
         }
         NameRefKind::Path(path) => complete_path(acc, db, module, path)?,
+        NameRefKind::DotAccess { receiver } => complete_dot(acc, db, receiver)?,
         NameRefKind::BareIdentInMod => {
             let name_range = name_ref.syntax().range();
             let top_node = name_ref
@@ -82,6 +93,8 @@ enum NameRefKind<'a> {
     /// NameRef is bare identifier at the module's root.
     /// Used for keyword completion
     BareIdentInMod,
+    /// NameRef is a field or method name following `receiver.`
+    DotAccess { receiver: ast::Expr<'a> },
 }
 
 fn classify_name_ref(name_ref: ast::NameRef) -> Option<NameRefKind> {
@@ -98,6 +111,16 @@ fn classify_name_ref(name_ref: ast::NameRef) -> Option<NameRefKind> {
     }
 
     let parent = name_ref.syntax().parent()?;
+    if let Some(field_expr) = ast::FieldExpr::cast(parent) {
+        if let Some(receiver) = field_expr.expr() {
+            return Some(NameRefKind::DotAccess { receiver });
+        }
+    }
+    if let Some(method_call) = ast::MethodCallExpr::cast(parent) {
+        if let Some(receiver) = method_call.expr() {
+            return Some(NameRefKind::DotAccess { receiver });
+        }
+    }
     if let Some(segment) = ast::PathSegment::cast(parent) {
         let path = segment.parent_path();
         if let Some(path) = Path::from_ast(path) {
@@ -117,18 +140,109 @@ fn classify_name_ref(name_ref: ast::NameRef) -> Option<NameRefKind> {
     None
 }
 
-fn complete_fn(name_ref: ast::NameRef, scopes: &FnScopes, acc: &mut Completions) {
+fn complete_fn(
+    name_ref: ast::NameRef,
+    scopes: &FnScopes,
+    acc: &mut Completions,
+    in_scope: &mut FxHashSet<String>,
+) {
     let mut shadowed = FxHashSet::default();
     scopes
         .scope_chain(name_ref.syntax())
         .flat_map(|scope| scopes.entries(scope).iter())
         .filter(|entry| shadowed.insert(entry.name()))
-        .for_each(|entry| CompletionItem::new(entry.name().to_string()).add_to(acc));
+        .for_each(|entry| {
+            in_scope.insert(entry.name().to_string());
+            CompletionItem::new(entry.name().to_string())
+                .kind(CompletionItemKind::Local)
+                .add_to(acc)
+        });
     if scopes.self_param.is_some() {
-        CompletionItem::new("self").add_to(acc);
+        in_scope.insert("self".to_string());
+        CompletionItem::new("self")
+            .kind(CompletionItemKind::Local)
+            .add_to(acc);
     }
 }
 
+/// For a `NameRef` that doesn't resolve to anything already in scope, looks
+/// up crate-wide symbols matching what's typed so far and offers them,
+/// bundling an edit that adds the needed `use`.
+fn complete_auto_import(
+    acc: &mut Completions,
+    db: &RootDatabase,
+    module: &hir::Module,
+    file: &SourceFileNode,
+    name_ref: ast::NameRef,
+    in_scope: &FxHashSet<String>,
+) -> Cancelable<()> {
+    let prefix = name_ref.syntax().text().to_string();
+    if prefix.is_empty() {
+        return Ok(());
+    }
+    let index = SymbolIndex::for_module(db, module)?;
+    for (name, candidates) in index.by_prefix(&prefix) {
+        if in_scope.contains(name) {
+            continue;
+        }
+        let mut visible = Vec::new();
+        for candidate in candidates {
+            if candidate.is_reachable_from(db, module)? {
+                visible.push(candidate);
+            }
+        }
+        // Several visible items can share a name (e.g. re-exports); prefer
+        // the one with the shortest import path.
+        let candidate = match visible.into_iter().min_by_key(|c| c.path.len()) {
+            Some(it) => it,
+            None => continue,
+        };
+        let mut item = CompletionItem::new(name.to_string())
+            .with_additional_edit(insert_use_edit(file, &candidate.path))
+            .set_relevance(-1);
+        if let Some(kind) = def_kind(db, candidate.def_id)? {
+            item = item.kind(kind);
+        }
+        item.add_to(acc);
+    }
+    Ok(())
+}
+
+fn insert_use_edit(file: &SourceFileNode, path: &[String]) -> TextEdit {
+    let mut builder = TextEditBuilder::default();
+    builder.insert(
+        use_insertion_offset(file),
+        format!("use {};\n", path.join("::")),
+    );
+    builder.finish()
+}
+
+/// Inner attributes (`#![...]`) must be the first items in a file, so a
+/// newly inserted `use` has to go after any of those rather than at offset
+/// `0`.
+fn use_insertion_offset(file: &SourceFileNode) -> TextUnit {
+    file.syntax()
+        .children()
+        .filter_map(ast::Attr::cast)
+        .take_while(|attr| attr.is_inner())
+        .last()
+        .map(|attr| attr.syntax().range().end())
+        .unwrap_or_else(|| 0.into())
+}
+
+/// Maps a resolved `Def` to the `CompletionItemKind` editors should use for
+/// it. Returns `None` for defs we don't have a dedicated icon for yet.
+fn def_kind(db: &RootDatabase, def_id: hir::DefId) -> Cancelable<Option<CompletionItemKind>> {
+    let kind = match def_id.resolve(db)? {
+        Def::Module(..) => CompletionItemKind::Module,
+        Def::Function(..) => CompletionItemKind::Function,
+        Def::Struct(..) => CompletionItemKind::Struct,
+        Def::Enum(..) => CompletionItemKind::Enum,
+        _ => return Ok(None),
+    };
+    Ok(Some(kind))
+}
+
 fn complete_path(
     acc: &mut Completions,
     db: &RootDatabase,
@@ -143,17 +257,153 @@ fn complete_path(
         None => return Ok(()),
         Some(it) => it,
     };
-    let target_module = match def_id.resolve(db)? {
-        Def::Module(it) => it,
-        _ => return Ok(()),
-    };
+    match def_id.resolve(db)? {
+        Def::Module(target_module) => complete_module_path(acc, db, target_module),
+        Def::Enum(e) => complete_enum_path(acc, db, e),
+        Def::Struct(s) => complete_assoc_items(acc, db, s.ty(db)),
+        Def::Trait(t) => complete_trait_path(acc, db, t),
+        _ => Ok(()),
+    }
+}
+
+fn complete_module_path(
+    acc: &mut Completions,
+    db: &RootDatabase,
+    target_module: hir::Module,
+) -> Cancelable<()> {
     let module_scope = target_module.scope(db)?;
-    module_scope
-        .entries()
-        .for_each(|(name, _res)| CompletionItem::new(name.to_string()).add_to(acc));
+    for (name, res) in module_scope.entries() {
+        let mut item = CompletionItem::new(name.to_string());
+        if let Some(def_id) = res.def_id {
+            if let Some(kind) = def_kind(db, def_id)? {
+                item = item.kind(kind);
+            }
+        }
+        item.add_to(acc);
+    }
+    Ok(())
+}
+
+/// `MyEnum::` completes its variants, plus any inherent associated
+/// functions and consts.
+fn complete_enum_path(acc: &mut Completions, db: &RootDatabase, e: hir::Enum) -> Cancelable<()> {
+    for variant in e.variants(db)? {
+        let name = variant.name(db).to_string();
+        let item = match variant.kind(db)? {
+            hir::StructKind::Tuple => CompletionItem::new(name.clone())
+                .lookup_by(name.clone())
+                .snippet(format!("{}($0)", name)),
+            hir::StructKind::Record => CompletionItem::new(name.clone())
+                .lookup_by(name.clone())
+                .snippet(format!("{} {{ $0 }}", name)),
+            hir::StructKind::Unit => CompletionItem::new(name),
+        };
+        item.kind(CompletionItemKind::EnumVariant).add_to(acc);
+    }
+    complete_assoc_items(acc, db, e.ty(db))
+}
+
+/// `SomeTrait::` completes the trait's associated functions and consts.
+fn complete_trait_path(acc: &mut Completions, db: &RootDatabase, t: hir::Trait) -> Cancelable<()> {
+    for item in t.items(db)? {
+        match item {
+            hir::TraitItem::Function(f) => CompletionItem::new(f.name(db).to_string())
+                .kind(CompletionItemKind::Function)
+                .add_to(acc),
+            hir::TraitItem::Const(c) => CompletionItem::new(c.name(db).to_string())
+                .kind(CompletionItemKind::Const)
+                .add_to(acc),
+        }
+    }
     Ok(())
 }
 
+/// Inherent associated functions and consts reachable as `Ty::name`.
+fn complete_assoc_items(acc: &mut Completions, db: &RootDatabase, ty: hir::Ty) -> Cancelable<()> {
+    for function in ty.methods(db)? {
+        let signature = function.signature(db);
+        // The complement of `complete_methods`: a `self`-taking method is
+        // only callable as `receiver.foo(...)`, not `Type::foo()`.
+        if signature.has_self_param() {
+            continue;
+        }
+        let name = function.name(db).to_string();
+        let n_params = signature.params().len();
+        CompletionItem::new(name.clone())
+            .lookup_by(name.clone())
+            .snippet(method_call_snippet(&name, n_params))
+            .kind(CompletionItemKind::Function)
+            .add_to(acc);
+    }
+    for konst in ty.consts(db)? {
+        CompletionItem::new(konst.name(db).to_string())
+            .kind(CompletionItemKind::Const)
+            .add_to(acc);
+    }
+    Ok(())
+}
+
+/// Completes fields and methods reachable on the type of `receiver`, for
+/// `receiver.$0`.
+fn complete_dot(acc: &mut Completions, db: &RootDatabase, receiver: ast::Expr) -> Cancelable<()> {
+    let fn_def = match receiver
+        .syntax()
+        .ancestors()
+        .take_while(|it| it.kind() != SOURCE_FILE && it.kind() != MODULE)
+        .find_map(ast::FnDef::cast)
+    {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let infer = hir::infer(db, fn_def)?;
+    let ty = match infer.type_of(receiver.syntax()) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    complete_fields(acc, db, &ty)?;
+    complete_methods(acc, db, &ty)?;
+    Ok(())
+}
+
+fn complete_fields(acc: &mut Completions, db: &RootDatabase, ty: &hir::Ty) -> Cancelable<()> {
+    if let Some(strukt) = ty.as_struct() {
+        for field in strukt.fields(db)? {
+            CompletionItem::new(field.name(db).to_string())
+                .kind(CompletionItemKind::Field)
+                .add_to(acc);
+        }
+    }
+    Ok(())
+}
+
+fn complete_methods(acc: &mut Completions, db: &RootDatabase, ty: &hir::Ty) -> Cancelable<()> {
+    for function in ty.methods(db)? {
+        let signature = function.signature(db);
+        // Only `self`-taking functions are callable as `receiver.foo(...)`;
+        // associated functions like `fn new() -> Self` go through `Type::`
+        // completion (see `complete_assoc_items`) instead.
+        if !signature.has_self_param() {
+            continue;
+        }
+        let name = function.name(db).to_string();
+        let n_params = signature.params().len();
+        CompletionItem::new(name.clone())
+            .lookup_by(name.clone())
+            .snippet(method_call_snippet(&name, n_params))
+            .kind(CompletionItemKind::Method)
+            .add_to(acc);
+    }
+    Ok(())
+}
+
+fn method_call_snippet(name: &str, n_params: usize) -> String {
+    let args = (1..=n_params)
+        .map(|i| format!("${}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", name, args)
+}
+
 fn complete_mod_item_snippets(acc: &mut Completions) {
     CompletionItem::new("Test function")
         .lookup_by("tfn")
@@ -164,9 +414,11 @@ fn ${1:feature}() {
     $0
 }",
         )
+        .kind(CompletionItemKind::Snippet)
         .add_to(acc);
     CompletionItem::new("pub(crate)")
         .snippet("pub(crate) $0")
+        .kind(CompletionItemKind::Snippet)
         .add_to(acc);
 }
 
@@ -249,14 +501,161 @@ fn complete_return(fn_def: ast::FnDef, name_ref: ast::NameRef) -> Option<Complet
 }
 
 fn keyword(kw: &str, snippet: &str) -> CompletionItem {
-    CompletionItem::new(kw).snippet(snippet).build()
+    CompletionItem::new(kw)
+        .snippet(snippet)
+        .kind(CompletionItemKind::Keyword)
+        .build()
 }
 
 fn complete_expr_snippets(acc: &mut Completions) {
     CompletionItem::new("pd")
         .snippet("eprintln!(\"$0 = {:?}\", $0);")
+        .kind(CompletionItemKind::Snippet)
         .add_to(acc);
     CompletionItem::new("ppd")
         .snippet("eprintln!(\"$0 = {:#?}\", $0);")
+        .kind(CompletionItemKind::Snippet)
         .add_to(acc);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file_with_position;
+
+    /// Runs completion at the `<|>` marker in `code` and asserts that every
+    /// label in `expected` is present, and every label in `absent` is not.
+    fn check_completion(code: &str, expected: &[&str], absent: &[&str]) {
+        let (analysis, position) = single_file_with_position(code);
+        let completions = analysis
+            .completions(position)
+            .unwrap()
+            .unwrap_or_default();
+        let labels: Vec<&str> = completions.iter().map(|it| it.label()).collect();
+        for label in expected {
+            assert!(
+                labels.contains(label),
+                "expected completion `{}`, got {:?}",
+                label,
+                labels
+            );
+        }
+        for label in absent {
+            assert!(
+                !labels.contains(label),
+                "did not expect completion `{}`, got {:?}",
+                label,
+                labels
+            );
+        }
+    }
+
+    #[test]
+    fn completes_fields_and_self_methods_after_dot() {
+        check_completion(
+            r#"
+            struct Foo { bar: u32 }
+            impl Foo {
+                fn baz(&self) -> u32 { self.bar }
+                fn new() -> Foo { Foo { bar: 0 } }
+            }
+            fn main() {
+                let foo = Foo::new();
+                foo.<|>
+            }
+            "#,
+            &["bar", "baz"],
+            // `new` takes no `self` and isn't callable as `foo.new()`.
+            &["new"],
+        );
+    }
+
+    #[test]
+    fn auto_imports_pub_item_from_another_module() {
+        check_completion(
+            r#"
+            mod other {
+                pub struct Frobnicator;
+            }
+            fn main() {
+                let _ = Frobnicat<|>
+            }
+            "#,
+            &["Frobnicator"],
+            &[],
+        );
+    }
+
+    #[test]
+    fn does_not_auto_import_private_item_from_another_module() {
+        check_completion(
+            r#"
+            mod other {
+                struct Frobnicator;
+            }
+            fn main() {
+                let _ = Frobnicat<|>
+            }
+            "#,
+            &[],
+            &["Frobnicator"],
+        );
+    }
+
+    #[test]
+    fn does_not_auto_import_pub_item_behind_private_module() {
+        check_completion(
+            r#"
+            mod a {
+                mod b {
+                    pub struct Frobnicator;
+                }
+            }
+            mod c {
+                fn main() {
+                    let _ = Frobnicat<|>
+                }
+            }
+            "#,
+            &[],
+            // `Frobnicator` is `pub`, but `b` is private, so `a::b::Frobnicator`
+            // isn't reachable from `c` and no `use` for it should be offered.
+            &["Frobnicator"],
+        );
+    }
+
+    #[test]
+    fn completes_enum_variants_in_path_position() {
+        check_completion(
+            r#"
+            enum Animal {
+                Dog,
+                Cat { weight: u32 },
+            }
+            fn main() {
+                Animal::<|>
+            }
+            "#,
+            &["Dog", "Cat"],
+            &[],
+        );
+    }
+
+    #[test]
+    fn completes_assoc_fns_but_not_self_methods_in_path_position() {
+        check_completion(
+            r#"
+            struct Foo { bar: u32 }
+            impl Foo {
+                fn new() -> Foo { Foo { bar: 0 } }
+                fn baz(&self) -> u32 { self.bar }
+            }
+            fn main() {
+                Foo::<|>
+            }
+            "#,
+            &["new"],
+            // `baz` takes `self` and is only callable as `receiver.baz()`.
+            &["baz"],
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! A crate-wide index from identifier to the definitions reachable under
+//! that name, used to power auto-import completion (finding a symbol that
+//! isn't in scope yet and suggesting a `use` for it).
+//!
+//! The index itself is crate-wide and doesn't know who is asking for it, so
+//! it stores, for each candidate, the declared `Visibility` of every module
+//! segment in its path (not just the leaf item) and lets the caller decide,
+//! per completion request, whether the whole path is actually reachable
+//! from the module the user is typing in.
+
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use hir::{self, Def, DefId, Visibility};
+
+use crate::Cancelable;
+
+/// A definition reachable under some name, together with the path one would
+/// `use` to import it and the visibility each segment of that path was
+/// declared with (outermost first).
+#[derive(Debug, Clone)]
+pub(crate) struct Candidate {
+    pub(crate) path: Vec<String>,
+    pub(crate) def_id: DefId,
+    visibilities: Vec<Visibility>,
+}
+
+impl Candidate {
+    /// Whether every module on `self.path` -- not just the item itself --
+    /// is visible from `from_module`. A path through a private submodule
+    /// doesn't compile even if the leaf item is `pub`.
+    pub(crate) fn is_reachable_from(
+        &self,
+        db: &impl SymbolsDatabase,
+        from_module: &hir::Module,
+    ) -> Cancelable<bool> {
+        for visibility in &self.visibilities {
+            if !visibility.is_visible_from(db, from_module)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SymbolIndex {
+    by_name: FxHashMap<String, Vec<Candidate>>,
+}
+
+#[salsa::query_group(SymbolsDatabaseStorage)]
+pub(crate) trait SymbolsDatabase: hir::db::HirDatabase {
+    /// Indexes `krate` once and memoizes the result; only recomputed when
+    /// something in the crate's module tree actually changes, so completion
+    /// doesn't re-walk the whole crate on every keystroke.
+    fn crate_symbol_index(&self, krate: hir::Crate) -> Cancelable<Arc<SymbolIndex>>;
+}
+
+fn crate_symbol_index(
+    db: &impl SymbolsDatabase,
+    krate: hir::Crate,
+) -> Cancelable<Arc<SymbolIndex>> {
+    let mut index = SymbolIndex::default();
+    let root = krate.root_module(db)?;
+    index.walk(db, root, Vec::new(), Vec::new())?;
+    Ok(Arc::new(index))
+}
+
+impl SymbolIndex {
+    /// Looks up (and, the first time, builds) the cached index for the
+    /// crate `module` belongs to.
+    pub(crate) fn for_module(
+        db: &impl SymbolsDatabase,
+        module: &hir::Module,
+    ) -> Cancelable<Arc<SymbolIndex>> {
+        let krate = module.krate(db)?;
+        db.crate_symbol_index(krate)
+    }
+
+    fn walk(
+        &mut self,
+        db: &impl SymbolsDatabase,
+        module: hir::Module,
+        path: Vec<String>,
+        visibilities: Vec<Visibility>,
+    ) -> Cancelable<()> {
+        for (name, res) in module.scope(db)?.entries() {
+            let def_id = match res.def_id {
+                Some(it) => it,
+                None => continue,
+            };
+            let mut item_path = path.clone();
+            item_path.push(name.to_string());
+            let mut item_visibilities = visibilities.clone();
+            item_visibilities.push(res.visibility);
+
+            // Recurse using this very resolution so the submodule's own
+            // visibility (just recorded above) carries into everything
+            // found underneath it.
+            if let Def::Module(child) = def_id.resolve(db)? {
+                self.walk(db, child, item_path.clone(), item_visibilities.clone())?;
+            }
+
+            self.by_name.entry(name.to_string()).or_default().push(Candidate {
+                path: item_path,
+                def_id,
+                visibilities: item_visibilities,
+            });
+        }
+        Ok(())
+    }
+
+    /// All `(name, candidates)` pairs whose name starts with `prefix`. The
+    /// candidates are not filtered by reachability here -- the index is
+    /// shared crate-wide, so that's the caller's job, relative to whatever
+    /// module is asking.
+    pub(crate) fn by_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a [Candidate])> + 'a {
+        self.by_name
+            .iter()
+            .filter(move |(name, _)| name.starts_with(prefix))
+            .map(|(name, candidates)| (name.as_str(), candidates.as_slice()))
+    }
+}